@@ -18,20 +18,38 @@ fn main() -> Result<(), anyhow::Error> {
 
 #[cfg(any(esp32c6, esp32h2))]
 mod garland {
-    use core::cell::Cell;
+    use core::cell::{Cell, RefCell};
     use core::pin::pin;
+    use core::time::Duration;
 
     use alloc::sync::Arc;
+    use alloc::vec::Vec;
 
     use esp_idf_matter::init_async_io;
+    use esp_idf_matter::matter::dm::clusters::decl::level_control as level_control_cluster;
     use esp_idf_matter::matter::dm::clusters::decl::on_off as on_off_cluster;
+    use esp_idf_matter::matter::dm::clusters::decl::power_source as power_source_cluster;
+    use esp_idf_matter::matter::dm::clusters::decl::temp_measurement as temp_measurement_cluster;
     use esp_idf_matter::matter::dm::clusters::desc::{self, ClusterHandler as _, DescHandler};
+    use esp_idf_matter::matter::dm::clusters::level_control::{
+        self, LevelControlHandler, LevelControlHooks,
+    };
     use esp_idf_matter::matter::dm::clusters::on_off::{
         self, EffectVariantEnum, OnOffHandler, OnOffHooks, StartUpOnOffEnum,
     };
+    use esp_idf_matter::matter::dm::clusters::power_source::{
+        self, PowerSourceHandler, PowerSourceHooks, PowerSourceStatusEnum,
+    };
+    use esp_idf_matter::matter::dm::clusters::temp_measurement::{
+        self, TempMeasurementHandler, TempMeasurementHooks,
+    };
     use esp_idf_matter::matter::dm::devices::test::{TEST_DEV_ATT, TEST_DEV_COMM, TEST_DEV_DET};
-    use esp_idf_matter::matter::dm::devices::DEV_TYPE_ON_OFF_LIGHT;
-    use esp_idf_matter::matter::dm::{Async, Cluster, Dataver, EmptyHandler, Endpoint, EpClMatcher, Node};
+    use esp_idf_matter::matter::dm::devices::{
+        DEV_TYPE_DIMMABLE_LIGHT, DEV_TYPE_POWER_SOURCE, DEV_TYPE_TEMP_SENSOR,
+    };
+    use esp_idf_matter::matter::dm::{
+        Async, Cluster, Dataver, EmptyHandler, Endpoint, EpClMatcher, Node,
+    };
     use esp_idf_matter::matter::error::Error;
     use esp_idf_matter::matter::tlv::Nullable;
     use esp_idf_matter::matter::utils::init::InitMaybeUninit;
@@ -41,12 +59,26 @@ mod garland {
 
     use esp_idf_svc::bt::reduce_bt_memory;
     use esp_idf_svc::eventloop::EspSystemEventLoop;
+    use esp_idf_svc::hal::adc::oneshot::config::AdcChannelConfig;
+    use esp_idf_svc::hal::adc::oneshot::{AdcChannelDriver, AdcDriver};
+    use esp_idf_svc::hal::adc::ADC1;
+    use esp_idf_svc::hal::gpio::Gpio2;
+    use esp_idf_svc::hal::ledc::config::TimerConfig;
+    use esp_idf_svc::hal::ledc::{LedcDriver, LedcTimerDriver};
     use esp_idf_svc::hal::peripherals::Peripherals;
     use esp_idf_svc::hal::task::block_on;
     use esp_idf_svc::hal::task::thread::ThreadSpawnConfiguration;
+    use esp_idf_svc::hal::units::Hertz;
     use esp_idf_svc::io::vfs::MountedEventfs;
     use esp_idf_svc::nvs::EspDefaultNvsPartition;
-    use esp_idf_svc::sys::{gpio_config, gpio_config_t, gpio_mode_t_GPIO_MODE_OUTPUT, gpio_set_level};
+    use esp_idf_svc::sys::{
+        gpio_config, gpio_config_t, gpio_mode_t_GPIO_MODE_OUTPUT, gpio_set_level,
+        temperature_sensor_config_t, temperature_sensor_enable, temperature_sensor_get_celsius,
+        temperature_sensor_handle_t, temperature_sensor_install,
+    };
+    use esp_idf_svc::timer::{EspAsyncTimer, EspTimerService, Task};
+
+    use embassy_futures::select::{select4, Either4};
 
     use log::{error, info};
 
@@ -58,15 +90,247 @@ mod garland {
     const BUMP_SIZE: usize = 13500;
     const GPIO_NUM: i32 = 18;
     const LIGHT_ENDPOINT_ID: u16 = 1;
+    const TEMP_ENDPOINT_ID: u16 = 2;
+    const POWER_ENDPOINT_ID: u16 = 3;
+
+    /// PWM frequency for the garland MOSFET gate drive, chosen well above the
+    /// range that causes visible flicker while staying low enough to keep
+    /// MOSFET switching losses negligible.
+    const PWM_FREQUENCY: Hertz = Hertz(3_000);
+
+    /// Matter Level Control `CurrentLevel` is defined over `1..=254`.
+    const MAX_LEVEL: u8 = 254;
+
+    /// Step interval for software off-with-effect fades.
+    const FADE_STEP: Duration = Duration::from_millis(50);
+
+    /// Drives the garland MOSFET, either via hardware PWM (preferred, gives
+    /// real dimming) or a plain GPIO on/off toggle when LEDC is unavailable.
+    enum Output {
+        Pwm(LedcDriver<'static>),
+        Gpio,
+    }
+
+    impl Output {
+        fn max_duty(&self) -> u32 {
+            match self {
+                Self::Pwm(pwm) => pwm.get_max_duty(),
+                Self::Gpio => 1,
+            }
+        }
+
+        fn set_duty(&mut self, duty: u32) {
+            match self {
+                Self::Pwm(pwm) => {
+                    if let Err(e) = pwm.set_duty(duty) {
+                        error!("Garland: LEDC set_duty failed: {e}");
+                    }
+                }
+                Self::Gpio => {
+                    let ret = unsafe { gpio_set_level(GPIO_NUM, i32::from(duty > 0)) };
+                    if ret != 0 {
+                        error!("Garland: gpio_set_level failed: {ret}");
+                    }
+                }
+            }
+        }
+
+        /// Maps a Matter `CurrentLevel` (1..=254) to the duty value this
+        /// output should be driven at: a linearly scaled PWM duty when real
+        /// dimming is available, or just "any non-zero level is on" for the
+        /// GPIO fallback, which has no headroom (`max_duty() == 1`) to
+        /// represent a scaled value and would otherwise round every level
+        /// below 254 down to zero.
+        fn duty_for_level(&self, level: u8) -> u32 {
+            match self {
+                Self::Pwm(_) => u32::from(level) * self.max_duty() / u32::from(MAX_LEVEL),
+                Self::Gpio => u32::from(level > 0),
+            }
+        }
+    }
 
     pub struct GarlandController {
-        state: Cell<bool>,
+        on: Cell<bool>,
+        level: Cell<u8>,
+        output: RefCell<Output>,
+        /// Used to mint a fresh `EspAsyncTimer` per fade rather than sharing
+        /// one behind a `RefCell`: a fade holds its timer across `.await`,
+        /// and two overlapping `OffWithEffect` calls (e.g. from different
+        /// fabrics) sharing one `RefCell<EspAsyncTimer>` would panic on a
+        /// double `borrow_mut`.
+        timer_service: EspTimerService<Task>,
+        /// Bumped on every command that should pre-empt an in-progress fade
+        /// (`On`, `Off`, `Toggle`, a new off-with-effect); a running fade checks
+        /// this after each step and abandons itself if it no longer matches.
+        fade_epoch: Cell<u64>,
+        /// Set by the thermal monitor while the chip is over temperature; forces
+        /// duty to zero regardless of the commissioned on/level state.
+        thermal_limited: Cell<bool>,
+        /// Set by the power monitor while the supply rail is below the
+        /// brownout threshold; forces duty to zero for the same reason.
+        power_limited: Cell<bool>,
+        /// OnOff cluster `OnTime`/`OffWaitTime` attributes, repurposed by the
+        /// scheduler as the configured dusk/late-night countdown lengths, in
+        /// minutes (see [`Scheduler`]).
+        on_time: Cell<u16>,
+        off_wait_time: Cell<u16>,
+        /// Bound once the scheduler is constructed so manual commands can reset
+        /// its countdown; absent only during the brief startup window.
+        scheduler: RefCell<Option<Arc<Scheduler>>>,
     }
 
     impl GarlandController {
-        pub fn new() -> Self {
+        pub fn new(output: Output, timer_service: EspTimerService<Task>) -> Self {
             Self {
-                state: Cell::new(false),
+                on: Cell::new(false),
+                level: Cell::new(MAX_LEVEL),
+                output: RefCell::new(output),
+                timer_service,
+                fade_epoch: Cell::new(0),
+                thermal_limited: Cell::new(false),
+                power_limited: Cell::new(false),
+                on_time: Cell::new(0),
+                off_wait_time: Cell::new(0),
+                scheduler: RefCell::new(None),
+            }
+        }
+
+        /// Wires up the scheduler so manual On/Off commands can notify it.
+        /// Called once, right after both are constructed in `matter()`.
+        pub fn bind_scheduler(&self, scheduler: Arc<Scheduler>) {
+            *self.scheduler.borrow_mut() = Some(scheduler);
+        }
+
+        /// Applies an on/off transition driven by the scheduler: cancels any
+        /// in-progress fade and drives the output, without touching the
+        /// scheduler's own state (it's already managing it).
+        fn apply_scheduled(&self, on: bool) {
+            self.begin_fade_epoch();
+            self.drive(on);
+        }
+
+        /// Re-arms automatic scheduling after a prior manual override
+        /// disarmed it, since rewriting `OnTime`/`OffWaitTime` is a clear
+        /// signal the controller wants the daily schedule running again.
+        fn reenable_schedule(&self) {
+            if let Some(scheduler) = self.scheduler.borrow().as_ref() {
+                scheduler.enable_auto();
+            }
+        }
+
+        /// Tells the scheduler the garland reached a new steady on/off state
+        /// outside of its own countdown, so its view doesn't diverge from
+        /// the hardware. Shared by the manual On/Off/Toggle path and a
+        /// completed off-with-effect fade.
+        fn notify_scheduler(&self, on: bool) {
+            if let Some(scheduler) = self.scheduler.borrow().as_ref() {
+                scheduler.note_manual(on);
+            }
+        }
+
+        /// Sets the on/off state and drives the physical output; shared by the
+        /// manual command path and the scheduler.
+        fn drive(&self, on: bool) {
+            self.on.set(on);
+            // Restore the last non-zero level when turning back on, as required
+            // by the Matter OnOff/Level Control coupling rules.
+            self.apply_level(if on { self.level.get() } else { 0 });
+            info!("Garland: {}", if on { "ON" } else { "OFF" });
+        }
+
+        /// Drives the physical output to the duty cycle corresponding to `level`,
+        /// scaled against the output's max duty, unless thermal throttling or a
+        /// supply brownout is currently forcing the output off.
+        fn apply_level(&self, level: u8) {
+            let duty = self.output.borrow().duty_for_level(level);
+            self.set_limited_duty(duty);
+        }
+
+        /// Drives the output to `duty`, clamping to zero if thermal
+        /// throttling or brownout protection is currently engaged. Shared by
+        /// `apply_level` and the fade ramp so a fade can never drive the
+        /// MOSFET past a safety cutoff the steady-state path would enforce.
+        fn set_limited_duty(&self, duty: u32) {
+            let duty = if self.thermal_limited.get() || self.power_limited.get() {
+                0
+            } else {
+                duty
+            };
+            self.output.borrow_mut().set_duty(duty);
+        }
+
+        /// Engages or clears thermal throttling, re-applying the current on/level
+        /// state so the garland resumes automatically once it cools.
+        pub fn set_thermal_limit(&self, limited: bool) {
+            if limited == self.thermal_limited.get() {
+                return;
+            }
+
+            self.thermal_limited.set(limited);
+            self.apply_level(if self.on.get() { self.level.get() } else { 0 });
+        }
+
+        /// Engages or clears the supply-brownout limit, re-applying the current
+        /// on/level state so the garland resumes automatically once the rail
+        /// recovers.
+        pub fn set_power_limit(&self, limited: bool) {
+            if limited == self.power_limited.get() {
+                return;
+            }
+
+            self.power_limited.set(limited);
+            self.apply_level(if self.on.get() { self.level.get() } else { 0 });
+        }
+
+        /// Sets `CurrentLevel` and, if the garland is on, re-applies the new duty
+        /// immediately.
+        pub fn set_level(&self, level: u8) {
+            let level = level.clamp(1, MAX_LEVEL);
+            self.level.set(level);
+            if self.on.get() {
+                self.apply_level(level);
+            }
+        }
+
+        /// Starts a new fade "epoch", invalidating any fade loop currently in
+        /// flight, and returns the epoch the caller should tag its own fade with.
+        fn begin_fade_epoch(&self) -> u64 {
+            let epoch = self.fade_epoch.get().wrapping_add(1);
+            self.fade_epoch.set(epoch);
+            epoch
+        }
+
+        /// Ramps the output duty linearly from `from_duty` to `to_duty` over
+        /// `duration`, bailing out early if `epoch` has been superseded by a
+        /// newer command.
+        async fn ramp_duty(&self, from_duty: u32, to_duty: u32, duration: Duration, epoch: u64) {
+            // Own this fade's timer rather than sharing one behind a
+            // RefCell: `after(...).await` below suspends with the timer
+            // borrowed, and a second concurrent fade (another fabric's
+            // OffWithEffect) sharing that RefCell would panic on the double
+            // borrow_mut.
+            let mut timer = match self.timer_service.timer_async() {
+                Ok(timer) => timer,
+                Err(e) => {
+                    error!("Garland: failed to create fade timer: {e}");
+                    return;
+                }
+            };
+
+            let steps = (duration.as_millis() / FADE_STEP.as_millis()).max(1) as i64;
+
+            for step in 1..=steps {
+                if self.fade_epoch.get() != epoch {
+                    return;
+                }
+
+                let duty = from_duty as i64 + (to_duty as i64 - from_duty as i64) * step / steps;
+                self.set_limited_duty(duty as u32);
+
+                if let Err(e) = timer.after(FADE_STEP).await {
+                    error!("Garland: fade timer error: {e}");
+                    return;
+                }
             }
         }
     }
@@ -74,26 +338,30 @@ mod garland {
     impl OnOffHooks for GarlandController {
         const CLUSTER: Cluster<'static> = on_off_cluster::FULL_CLUSTER
             .with_revision(6)
-            .with_attrs(with!(required; on_off_cluster::AttributeId::OnOff))
+            .with_attrs(with!(
+                required;
+                on_off_cluster::AttributeId::OnOff;
+                optional;
+                on_off_cluster::AttributeId::OnTime | on_off_cluster::AttributeId::OffWaitTime
+            ))
             .with_cmds(with!(
                 on_off_cluster::CommandId::Off
                     | on_off_cluster::CommandId::On
                     | on_off_cluster::CommandId::Toggle
+                    | on_off_cluster::CommandId::OffWithEffect
             ));
 
         fn on_off(&self) -> bool {
-            self.state.get()
+            self.on.get()
         }
 
         fn set_on_off(&self, on: bool) {
-            self.state.set(on);
-            let level = if on { 1 } else { 0 };
-            let ret = unsafe { gpio_set_level(GPIO_NUM, level) };
-            if ret == 0 {
-                info!("Garland: {}", if on { "ON" } else { "OFF" });
-            } else {
-                error!("Garland: {} FAILED: {}", if on { "ON" } else { "OFF" }, ret);
-            }
+            // Pre-empt any in-progress off-with-effect fade: a plain On/Off/
+            // Toggle always wins immediately, Matter spec or not.
+            self.apply_scheduled(on);
+
+            // A manual command always overrides the schedule's own countdown.
+            self.notify_scheduler(on);
         }
 
         fn start_up_on_off(&self) -> Nullable<StartUpOnOffEnum> {
@@ -104,8 +372,505 @@ mod garland {
             Ok(())
         }
 
-        async fn handle_off_with_effect(&self, _effect: EffectVariantEnum) {
-            self.set_on_off(false);
+        fn on_time(&self) -> u16 {
+            self.on_time.get()
+        }
+
+        fn set_on_time(&self, value: u16) -> Result<(), Error> {
+            self.on_time.set(value);
+            self.reenable_schedule();
+            Ok(())
+        }
+
+        fn off_wait_time(&self) -> u16 {
+            self.off_wait_time.get()
+        }
+
+        fn set_off_wait_time(&self, value: u16) -> Result<(), Error> {
+            self.off_wait_time.set(value);
+            self.reenable_schedule();
+            Ok(())
+        }
+
+        async fn handle_off_with_effect(&self, effect: EffectVariantEnum) {
+            // Already off: nothing to fade from, and ramping from the stored
+            // level here would flash the output to full duty before fading.
+            if !self.on.get() {
+                return;
+            }
+
+            let epoch = self.begin_fade_epoch();
+            let max_duty = self.output.borrow().max_duty();
+            let current_duty = self.output.borrow().duty_for_level(self.level.get());
+
+            match effect {
+                EffectVariantEnum::DelayedOffFastFade => {
+                    self.ramp_duty(current_duty, 0, Duration::from_millis(800), epoch)
+                        .await;
+                }
+                EffectVariantEnum::NoFade => {
+                    self.set_limited_duty(0);
+                }
+                EffectVariantEnum::DelayedOffSlowFade => {
+                    self.ramp_duty(current_duty, 0, Duration::from_millis(12_800), epoch)
+                        .await;
+                }
+                EffectVariantEnum::DyingLightFadeOff => {
+                    let bump = (current_duty + current_duty / 5).min(max_duty);
+                    self.ramp_duty(current_duty, bump, Duration::from_millis(500), epoch)
+                        .await;
+                    self.ramp_duty(bump, 0, Duration::from_millis(1_000), epoch)
+                        .await;
+                }
+            }
+
+            // Only the fade that hasn't been superseded gets to flip the OnOff
+            // state; a cancelled fade must leave `on` untouched.
+            if self.fade_epoch.get() == epoch {
+                self.on.set(false);
+
+                // Keep the scheduler's view in sync, same as a manual Off:
+                // otherwise it still believes the garland is On and, once
+                // armed, its next auto-off would fire against a garland
+                // that's already off.
+                self.notify_scheduler(false);
+            }
+        }
+    }
+
+    impl LevelControlHooks for GarlandController {
+        const CLUSTER: Cluster<'static> = level_control_cluster::FULL_CLUSTER
+            .with_revision(5)
+            .with_attrs(with!(required; level_control_cluster::AttributeId::CurrentLevel))
+            .with_cmds(with!(
+                level_control_cluster::CommandId::MoveToLevel
+                    | level_control_cluster::CommandId::MoveToLevelWithOnOff
+            ));
+
+        fn current_level(&self) -> Nullable<u8> {
+            Nullable::some(self.level.get())
+        }
+
+        async fn handle_move_to_level(&self, level: u8, with_on_off: bool) {
+            self.set_level(level);
+
+            if with_on_off && !self.on.get() {
+                self.set_on_off(true);
+            }
+        }
+    }
+
+    /// Sample interval for the internal chip temperature sensor.
+    const TEMP_SAMPLE_INTERVAL: Duration = Duration::from_secs(5);
+    /// Engage thermal throttling above this die temperature.
+    const TEMP_THROTTLE_ENGAGE_C: f32 = 65.0;
+    /// Only clear throttling once the die has cooled below this temperature,
+    /// so we don't chatter on/off right at the boundary.
+    const TEMP_THROTTLE_CLEAR_C: f32 = 60.0;
+    /// Conservative operating range for the ESP32-C6 internal temperature
+    /// sensor driver.
+    const TEMP_SENSOR_RANGE_MIN_C: i32 = -10;
+    const TEMP_SENSOR_RANGE_MAX_C: i32 = 80;
+
+    /// Backs the Temperature Measurement endpoint with the SoC's internal
+    /// die temperature sensor.
+    pub struct TemperatureMonitor {
+        measured_centidegrees: Cell<i16>,
+        throttled: Cell<bool>,
+    }
+
+    impl TemperatureMonitor {
+        pub fn new() -> Self {
+            Self {
+                measured_centidegrees: Cell::new(0),
+                throttled: Cell::new(false),
+            }
+        }
+    }
+
+    impl TempMeasurementHooks for TemperatureMonitor {
+        const CLUSTER: Cluster<'static> = temp_measurement_cluster::FULL_CLUSTER
+            .with_revision(4)
+            .with_attrs(with!(required; temp_measurement_cluster::AttributeId::MeasuredValue));
+
+        fn measured_value(&self) -> Nullable<i16> {
+            Nullable::some(self.measured_centidegrees.get())
+        }
+    }
+
+    /// Installs and enables the ESP32-C6/H2 internal temperature sensor.
+    fn init_temp_sensor() -> Option<temperature_sensor_handle_t> {
+        let mut config: temperature_sensor_config_t = unsafe { core::mem::zeroed() };
+        config.range_min = TEMP_SENSOR_RANGE_MIN_C;
+        config.range_max = TEMP_SENSOR_RANGE_MAX_C;
+
+        let mut handle: temperature_sensor_handle_t = core::ptr::null_mut();
+
+        let ret = unsafe { temperature_sensor_install(&config, &mut handle) };
+        if ret != 0 {
+            error!("Temp sensor: install failed: {ret}");
+            return None;
+        }
+
+        let ret = unsafe { temperature_sensor_enable(handle) };
+        if ret != 0 {
+            error!("Temp sensor: enable failed: {ret}");
+            return None;
+        }
+
+        Some(handle)
+    }
+
+    fn read_temp_c(handle: temperature_sensor_handle_t) -> Option<f32> {
+        let mut celsius = 0.0f32;
+        let ret = unsafe { temperature_sensor_get_celsius(handle, &mut celsius) };
+        if ret != 0 {
+            error!("Temp sensor: read failed: {ret}");
+            return None;
+        }
+
+        Some(celsius)
+    }
+
+    /// Periodically samples the internal temperature sensor, publishes it to
+    /// the Temperature Measurement cluster and throttles the garland PWM
+    /// output (with hysteresis) to protect the MOSFET if the die gets hot.
+    ///
+    /// Never returns: this task is raced against the Matter stack in
+    /// `matter()`'s `select4`, and a failure here should degrade thermal
+    /// protection, not tear down Matter along with it. Errors are logged and
+    /// swallowed instead of propagated.
+    async fn run_temp_monitor(
+        garland: Arc<GarlandController>,
+        monitor: Arc<TemperatureMonitor>,
+        mut timer: EspAsyncTimer,
+    ) -> Result<(), anyhow::Error> {
+        let Some(handle) = init_temp_sensor() else {
+            error!("Temp sensor: unavailable, thermal protection disabled for this boot");
+            loop {
+                if let Err(e) = timer.after(TEMP_SAMPLE_INTERVAL).await {
+                    error!("Temp sensor: timer error: {e}");
+                }
+            }
+        };
+
+        loop {
+            if let Some(celsius) = read_temp_c(handle) {
+                monitor.measured_centidegrees.set((celsius * 100.0) as i16);
+
+                let should_throttle = if monitor.throttled.get() {
+                    celsius > TEMP_THROTTLE_CLEAR_C
+                } else {
+                    celsius > TEMP_THROTTLE_ENGAGE_C
+                };
+
+                if should_throttle != monitor.throttled.get() {
+                    monitor.throttled.set(should_throttle);
+                    garland.set_thermal_limit(should_throttle);
+
+                    if should_throttle {
+                        error!("Garland: thermal throttle engaged at {celsius:.1}C");
+                    } else {
+                        info!("Garland: thermal throttle cleared at {celsius:.1}C");
+                    }
+                }
+            }
+
+            if let Err(e) = timer.after(TEMP_SAMPLE_INTERVAL).await {
+                error!("Temp sensor: timer error: {e}");
+            }
+        }
+    }
+
+    /// Tick period for the dusk/late-night on/off scheduler.
+    const SCHEDULER_TICK: Duration = Duration::from_secs(1);
+    /// NVS key the scheduler's countdown state is persisted under, separate
+    /// from the cluster attribute persistence the Matter stack already does.
+    const SCHEDULE_NVS_KEY: &str = "garland_sched";
+
+    #[derive(Clone, Copy, PartialEq, Eq)]
+    enum ScheduleState {
+        WaitingOn(u32),
+        On,
+        WaitingOff(u32),
+        Off,
+    }
+
+    impl ScheduleState {
+        fn encode(self) -> [u8; 5] {
+            let (tag, secs) = match self {
+                Self::WaitingOn(secs) => (0u8, secs),
+                Self::On => (1u8, 0),
+                Self::WaitingOff(secs) => (2u8, secs),
+                Self::Off => (3u8, 0),
+            };
+
+            let mut buf = [0u8; 5];
+            buf[0] = tag;
+            buf[1..].copy_from_slice(&secs.to_le_bytes());
+            buf
+        }
+
+        fn decode(buf: &[u8]) -> Option<Self> {
+            let secs = u32::from_le_bytes(buf.get(1..5)?.try_into().ok()?);
+
+            match buf.first()? {
+                0 => Some(Self::WaitingOn(secs)),
+                1 => Some(Self::On),
+                2 => Some(Self::WaitingOff(secs)),
+                3 => Some(Self::Off),
+                _ => None,
+            }
+        }
+    }
+
+    /// Turns the garland on and off on a recurring schedule via a 1 Hz
+    /// countdown state machine, modeled on the delayed-transition design the
+    /// UPS firmware uses for its own charge-controller states. The countdown
+    /// lengths come from the OnOff cluster's `OnTime`/`OffWaitTime`
+    /// attributes, repurposed here as this firmware's own schedule
+    /// configuration — *minutes* until the next auto-on / auto-off, not
+    /// their Matter "timed on" meaning of seconds — so the schedule can be
+    /// set by any Thread controller without a bespoke cluster. Minutes were
+    /// chosen over seconds because the attributes are `u16`: a seconds
+    /// countdown tops out at ~18 hours, too short to span a full dusk-to-
+    /// late-night day; minutes reach ~45 days.
+    ///
+    /// `Off`/`On` only re-arm the opposite countdown while `auto` is set.
+    /// A manual On/Off command (`note_manual`) clears it, so it sticks until
+    /// the schedule is reconfigured instead of being silently undone by the
+    /// countdown the schedule itself would otherwise re-arm.
+    pub struct Scheduler {
+        state: Cell<ScheduleState>,
+        auto: Cell<bool>,
+        kvs: EspKvBlobStore,
+    }
+
+    impl Scheduler {
+        pub fn new(kvs: EspKvBlobStore) -> Self {
+            let (state, auto) = kvs
+                .get(SCHEDULE_NVS_KEY)
+                .ok()
+                .flatten()
+                .and_then(|buf| Some((ScheduleState::decode(&buf)?, *buf.get(5)? != 0)))
+                .unwrap_or((ScheduleState::Off, true));
+
+            Self {
+                state: Cell::new(state),
+                auto: Cell::new(auto),
+                kvs,
+            }
+        }
+
+        fn persist(&self) {
+            let mut buf = [0u8; 6];
+            buf[..5].copy_from_slice(&self.state.get().encode());
+            buf[5] = u8::from(self.auto.get());
+
+            if let Err(e) = self.kvs.set(SCHEDULE_NVS_KEY, &buf) {
+                error!("Scheduler: failed to persist schedule state: {e:?}");
+            }
+        }
+
+        /// A manual On/Off command always wins immediately, resetting the
+        /// countdown as if the schedule had just reached that steady state,
+        /// and disarms automatic re-scheduling until `enable_auto` is called
+        /// again so the override isn't undone by the opposite countdown.
+        fn note_manual(&self, on: bool) {
+            self.state.set(if on {
+                ScheduleState::On
+            } else {
+                ScheduleState::Off
+            });
+            self.auto.set(false);
+            self.persist();
+        }
+
+        /// Re-arms automatic scheduling after it was disarmed by a manual
+        /// override. Called whenever the schedule's own configuration
+        /// (`OnTime`/`OffWaitTime`) is rewritten, since that's a clear signal
+        /// the controller wants the daily schedule running again.
+        fn enable_auto(&self) {
+            self.auto.set(true);
+            self.persist();
+        }
+    }
+
+    /// Ticks the scheduler at 1 Hz: arms a countdown from the `OnTime`/
+    /// `OffWaitTime` attributes whenever the garland is steady, automatic
+    /// scheduling hasn't been disarmed by a manual override, and the
+    /// relevant attribute is configured; drives the garland once a countdown
+    /// reaches zero.
+    ///
+    /// Never returns: raced against the Matter stack in `matter()`'s
+    /// `select4`, so a timer hiccup degrades the schedule rather than
+    /// tearing down Matter. Errors are logged and swallowed.
+    async fn run_scheduler(
+        garland: Arc<GarlandController>,
+        scheduler: Arc<Scheduler>,
+        mut timer: EspAsyncTimer,
+    ) -> Result<(), anyhow::Error> {
+        loop {
+            if let Err(e) = timer.after(SCHEDULER_TICK).await {
+                error!("Scheduler: timer error: {e}");
+                continue;
+            }
+
+            let current = scheduler.state.get();
+            let auto = scheduler.auto.get();
+            let next = match current {
+                ScheduleState::Off if auto && garland.on_time() > 0 => {
+                    ScheduleState::WaitingOn(u32::from(garland.on_time()) * 60)
+                }
+                ScheduleState::On if auto && garland.off_wait_time() > 0 => {
+                    ScheduleState::WaitingOff(u32::from(garland.off_wait_time()) * 60)
+                }
+                ScheduleState::WaitingOn(1) => {
+                    garland.apply_scheduled(true);
+                    ScheduleState::On
+                }
+                ScheduleState::WaitingOn(secs) => ScheduleState::WaitingOn(secs - 1),
+                ScheduleState::WaitingOff(1) => {
+                    garland.apply_scheduled(false);
+                    ScheduleState::Off
+                }
+                ScheduleState::WaitingOff(secs) => ScheduleState::WaitingOff(secs - 1),
+                steady => steady,
+            };
+
+            if next != current {
+                scheduler.state.set(next);
+
+                // Only persist the steady states: a countdown changes every
+                // tick, and writing NVS once a second would wear out the
+                // flash over a long dusk/late-night delay for no benefit —
+                // on reboot mid-countdown it's fine to fall back to the last
+                // known steady state and re-arm from there.
+                if matches!(next, ScheduleState::On | ScheduleState::Off) {
+                    scheduler.persist();
+                }
+            }
+        }
+    }
+
+    /// NVS key a pre-provisioned Thread operational dataset TLV is stored
+    /// under. Flashed in out-of-band (manufacturing, or a re-flash of a
+    /// previously-commissioned device) so the node can rejoin its Thread
+    /// network immediately instead of commissioning over BLE again.
+    const THREAD_DATASET_NVS_KEY: &str = "thread_dataset";
+
+    /// Reads a pre-provisioned Thread operational dataset TLV from NVS, if
+    /// one was flashed in for this device.
+    fn load_thread_dataset(kvs: &EspKvBlobStore) -> Option<Vec<u8>> {
+        kvs.get(THREAD_DATASET_NVS_KEY).ok().flatten()
+    }
+
+    /// Sample interval for the supply rail voltage monitor.
+    const POWER_SAMPLE_INTERVAL: Duration = Duration::from_secs(2);
+    /// Resistor-divider scale factor between the sensed ADC pin and the
+    /// actual supply rail, e.g. a 10k/2k divider scales the rail down to
+    /// roughly a sixth, so the ADC's 0-3.3V range covers a 0-20V rail.
+    const POWER_DIVIDER_RATIO: f32 = 6.06;
+    /// Below this rail voltage, cut the garland output to zero duty to shed
+    /// load and flag the Power Source cluster as unavailable. The cut is
+    /// immediate; no fade is applied.
+    const BROWNOUT_ENGAGE_MILLIVOLTS: u32 = 10_500;
+    /// Only clear the brownout limit once the rail has recovered past this
+    /// (higher) voltage, so we don't chatter right at the boundary.
+    const BROWNOUT_CLEAR_MILLIVOLTS: u32 = 11_000;
+
+    /// Backs the Power Source endpoint with a resistor-divider reading of the
+    /// garland's supply rail, and drives the brownout protection hysteresis.
+    pub struct PowerMonitor {
+        rail_millivolts: Cell<u32>,
+        brownout: Cell<bool>,
+    }
+
+    impl PowerMonitor {
+        pub fn new() -> Self {
+            Self {
+                rail_millivolts: Cell::new(0),
+                brownout: Cell::new(false),
+            }
+        }
+    }
+
+    impl PowerSourceHooks for PowerMonitor {
+        const CLUSTER: Cluster<'static> = power_source_cluster::FULL_CLUSTER
+            .with_revision(2)
+            .with_attrs(with!(
+                required;
+                power_source_cluster::AttributeId::Status
+                    | power_source_cluster::AttributeId::Order
+                    | power_source_cluster::AttributeId::Description;
+                optional;
+                power_source_cluster::AttributeId::WiredAssessedInputVoltage
+            ));
+
+        fn status(&self) -> PowerSourceStatusEnum {
+            if self.brownout.get() {
+                PowerSourceStatusEnum::Unavailable
+            } else {
+                PowerSourceStatusEnum::Active
+            }
+        }
+
+        fn order(&self) -> u8 {
+            0
+        }
+
+        fn description(&self) -> &str {
+            "Garland supply rail"
+        }
+
+        fn wired_assessed_input_voltage(&self) -> Nullable<u32> {
+            Nullable::some(self.rail_millivolts.get())
+        }
+    }
+
+    /// Periodically samples the supply rail through a resistor-divider ADC
+    /// channel, publishes it to the Power Source cluster and, with
+    /// hysteresis, switches the garland output off (and back on) to protect
+    /// against brownout. Both switches take effect at once, without a fade.
+    ///
+    /// Never returns: raced against the Matter stack in `matter()`'s
+    /// `select4`, so a timer hiccup degrades power monitoring rather than
+    /// tearing down Matter. Errors are logged and swallowed.
+    async fn run_power_monitor(
+        garland: Arc<GarlandController>,
+        monitor: Arc<PowerMonitor>,
+        mut adc: AdcChannelDriver<'static, Gpio2, AdcDriver<'static, ADC1>>,
+        mut timer: EspAsyncTimer,
+    ) -> Result<(), anyhow::Error> {
+        loop {
+            match adc.read() {
+                Ok(adc_millivolts) => {
+                    let rail_millivolts = (adc_millivolts as f32 * POWER_DIVIDER_RATIO) as u32;
+                    monitor.rail_millivolts.set(rail_millivolts);
+
+                    let should_limit = if monitor.brownout.get() {
+                        rail_millivolts < BROWNOUT_CLEAR_MILLIVOLTS
+                    } else {
+                        rail_millivolts < BROWNOUT_ENGAGE_MILLIVOLTS
+                    };
+
+                    if should_limit != monitor.brownout.get() {
+                        monitor.brownout.set(should_limit);
+                        garland.set_power_limit(should_limit);
+
+                        if should_limit {
+                            error!("Garland: brownout protection engaged at {rail_millivolts}mV");
+                        } else {
+                            info!("Garland: brownout protection cleared at {rail_millivolts}mV");
+                        }
+                    }
+                }
+                Err(e) => error!("Power monitor: ADC read failed: {e}"),
+            }
+
+            if let Err(e) = timer.after(POWER_SAMPLE_INTERVAL).await {
+                error!("Power monitor: timer error: {e}");
+            }
         }
     }
 
@@ -117,8 +882,28 @@ mod garland {
             EspThreadMatterStack::<0, ()>::root_endpoint(),
             Endpoint {
                 id: LIGHT_ENDPOINT_ID,
-                device_types: devices!(DEV_TYPE_ON_OFF_LIGHT),
-                clusters: clusters!(DescHandler::CLUSTER, GarlandController::CLUSTER),
+                device_types: devices!(DEV_TYPE_DIMMABLE_LIGHT),
+                clusters: clusters!(
+                    DescHandler::CLUSTER,
+                    <GarlandController as OnOffHooks>::CLUSTER,
+                    <GarlandController as LevelControlHooks>::CLUSTER
+                ),
+            },
+            Endpoint {
+                id: TEMP_ENDPOINT_ID,
+                device_types: devices!(DEV_TYPE_TEMP_SENSOR),
+                clusters: clusters!(
+                    DescHandler::CLUSTER,
+                    <TemperatureMonitor as TempMeasurementHooks>::CLUSTER
+                ),
+            },
+            Endpoint {
+                id: POWER_ENDPOINT_ID,
+                device_types: devices!(DEV_TYPE_POWER_SOURCE),
+                clusters: clusters!(
+                    DescHandler::CLUSTER,
+                    <PowerMonitor as PowerSourceHooks>::CLUSTER
+                ),
             },
         ],
     };
@@ -177,58 +962,206 @@ mod garland {
 
         info!("Basics initialized");
 
-        // Initialize GPIO18 for MOSFET-controlled garland
-        let io_conf = gpio_config_t {
-            pin_bit_mask: 1u64 << GPIO_NUM,
-            mode: gpio_mode_t_GPIO_MODE_OUTPUT,
-            pull_up_en: 0,
-            pull_down_en: 0,
-            intr_type: 0,
+        // Drive GPIO18 with hardware PWM (LEDC) so brightness can be dimmed
+        // instead of just switched; fall back to a plain on/off GPIO if the
+        // LEDC peripheral can't be brought up.
+        let pwm = LedcTimerDriver::new(
+            peripherals.ledc.timer0,
+            &TimerConfig::new().frequency(PWM_FREQUENCY),
+        )
+        .and_then(|timer_driver| {
+            LedcDriver::new(
+                peripherals.ledc.channel0,
+                timer_driver,
+                peripherals.pins.gpio18,
+            )
+        });
+
+        let output = match pwm {
+            Ok(pwm) => {
+                info!("GPIO18 initialized for garland PWM (LEDC)");
+                Output::Pwm(pwm)
+            }
+            Err(e) => {
+                error!("LEDC init failed ({e}), falling back to on/off GPIO control");
+
+                let io_conf = gpio_config_t {
+                    pin_bit_mask: 1u64 << GPIO_NUM,
+                    mode: gpio_mode_t_GPIO_MODE_OUTPUT,
+                    pull_up_en: 0,
+                    pull_down_en: 0,
+                    intr_type: 0,
+                };
+
+                unsafe {
+                    gpio_config(&io_conf);
+                }
+
+                info!("GPIO18 initialized for garland control (fallback)");
+                Output::Gpio
+            }
         };
 
-        unsafe {
-            gpio_config(&io_conf);
-        }
+        let fade_timer_service = EspTimerService::new()?;
+        let garland = Arc::new(GarlandController::new(output, fade_timer_service));
 
-        let garland = GarlandController::new();
-        info!("GPIO18 initialized for garland control");
+        // Sense the supply rail through a resistor divider on a spare ADC
+        // pin, as the UPS firmware does for its input/battery/output rails.
+        let power_adc = AdcDriver::new(peripherals.adc1)?;
+        let power_channel =
+            AdcChannelDriver::new(power_adc, peripherals.pins.gpio2, &AdcChannelConfig::new())?;
 
         let on_off = OnOffHandler::new_standalone(
             Dataver::new_rand(stack.matter().rand()),
             LIGHT_ENDPOINT_ID,
-            garland,
+            garland.clone(),
+        );
+
+        let level_control = LevelControlHandler::new_standalone(
+            Dataver::new_rand(stack.matter().rand()),
+            LIGHT_ENDPOINT_ID,
+            garland.clone(),
+        );
+
+        let temp_monitor = Arc::new(TemperatureMonitor::new());
+
+        let temp_measurement = TempMeasurementHandler::new_standalone(
+            Dataver::new_rand(stack.matter().rand()),
+            TEMP_ENDPOINT_ID,
+            temp_monitor.clone(),
+        );
+
+        let schedule_kvs = EspKvBlobStore::new_default(nvs.clone())?;
+        let scheduler = Arc::new(Scheduler::new(schedule_kvs));
+        garland.bind_scheduler(scheduler.clone());
+
+        let power_monitor = Arc::new(PowerMonitor::new());
+
+        let power_source = PowerSourceHandler::new_standalone(
+            Dataver::new_rand(stack.matter().rand()),
+            POWER_ENDPOINT_ID,
+            power_monitor.clone(),
         );
 
         let handler = EmptyHandler
             .chain(
                 EpClMatcher::new(
                     Some(LIGHT_ENDPOINT_ID),
-                    Some(GarlandController::CLUSTER.id),
+                    Some(<GarlandController as OnOffHooks>::CLUSTER.id),
                 ),
                 on_off::HandlerAsyncAdaptor(&on_off),
             )
+            .chain(
+                EpClMatcher::new(
+                    Some(LIGHT_ENDPOINT_ID),
+                    Some(<GarlandController as LevelControlHooks>::CLUSTER.id),
+                ),
+                level_control::HandlerAsyncAdaptor(&level_control),
+            )
             .chain(
                 EpClMatcher::new(Some(LIGHT_ENDPOINT_ID), Some(DescHandler::CLUSTER.id)),
                 Async(desc::DescHandler::new(Dataver::new_rand(stack.matter().rand())).adapt()),
+            )
+            .chain(
+                EpClMatcher::new(
+                    Some(TEMP_ENDPOINT_ID),
+                    Some(<TemperatureMonitor as TempMeasurementHooks>::CLUSTER.id),
+                ),
+                temp_measurement::HandlerAsyncAdaptor(&temp_measurement),
+            )
+            .chain(
+                EpClMatcher::new(Some(TEMP_ENDPOINT_ID), Some(DescHandler::CLUSTER.id)),
+                Async(desc::DescHandler::new(Dataver::new_rand(stack.matter().rand())).adapt()),
+            )
+            .chain(
+                EpClMatcher::new(
+                    Some(POWER_ENDPOINT_ID),
+                    Some(<PowerMonitor as PowerSourceHooks>::CLUSTER.id),
+                ),
+                power_source::HandlerAsyncAdaptor(&power_source),
+            )
+            .chain(
+                EpClMatcher::new(Some(POWER_ENDPOINT_ID), Some(DescHandler::CLUSTER.id)),
+                Async(desc::DescHandler::new(Dataver::new_rand(stack.matter().rand())).adapt()),
             );
 
         info!("Handler initialized");
 
-        let kvs = EspKvBlobStore::new_default(nvs.clone())?;
-        let persist = stack
-            .create_persist_with_comm_window(kvs)
-            .await?;
+        let thread_dataset_kvs = EspKvBlobStore::new_default(nvs.clone())?;
+        let thread_dataset = load_thread_dataset(&thread_dataset_kvs);
+
+        let mut esp_thread = EspMatterThread::new(
+            peripherals.modem,
+            sysloop,
+            nvs.clone(),
+            mounted_event_fs,
+            stack,
+        );
+
+        // Try to apply the pre-provisioned dataset before deciding whether to
+        // skip the BLE commissioning window: if applying it fails, fall back
+        // to commissioning over BLE instead of leaving the device
+        // unreachable over both Thread and BLE.
+        let skip_ble = match &thread_dataset {
+            Some(dataset) => match esp_thread.set_active_dataset_tlv(dataset) {
+                Ok(()) => {
+                    info!("Pre-provisioned Thread dataset found, skipping BLE commissioning");
+                    true
+                }
+                Err(e) => {
+                    error!(
+                        "Failed to apply pre-provisioned Thread dataset ({e:?}), \
+                         falling back to BLE commissioning"
+                    );
+                    false
+                }
+            },
+            None => {
+                info!("No pre-provisioned Thread dataset, commissioning over BLE");
+                false
+            }
+        };
+
+        let kvs = EspKvBlobStore::new_default(nvs)?;
+        let persist = if skip_ble {
+            // Already on a known Thread network: no need to hold a BLE
+            // commissioning window open.
+            stack.create_persist(kvs).await?
+        } else {
+            stack.create_persist_with_comm_window(kvs).await?
+        };
+
+        let matter = pin!(stack.run_coex(esp_thread, &persist, (NODE, handler), ()));
+
+        let temp_timer = EspTimerService::new()?.timer_async()?;
+        let temp_monitor_task = pin!(run_temp_monitor(garland.clone(), temp_monitor, temp_timer));
 
-        let matter = pin!(stack.run_coex(
-            EspMatterThread::new(peripherals.modem, sysloop, nvs, mounted_event_fs, stack),
-            &persist,
-            (NODE, handler),
-            (),
+        let scheduler_timer = EspTimerService::new()?.timer_async()?;
+        let scheduler_task = pin!(run_scheduler(garland.clone(), scheduler, scheduler_timer));
+
+        let power_timer = EspTimerService::new()?.timer_async()?;
+        let power_monitor_task = pin!(run_power_monitor(
+            garland.clone(),
+            power_monitor,
+            power_channel,
+            power_timer
         ));
 
         info!("About to run Matter");
 
-        matter.await?;
+        match select4(
+            matter,
+            temp_monitor_task,
+            scheduler_task,
+            power_monitor_task,
+        )
+        .await
+        {
+            Either4::First(result) => result?,
+            Either4::Second(result) => result?,
+            Either4::Third(result) => result?,
+            Either4::Fourth(result) => result?,
+        }
 
         Ok(())
     }